@@ -0,0 +1,18 @@
+//! Thin indirection over the threading/channel primitives the reader and
+//! writer threads are built on. Swapping this one module's imports under
+//! the `shuttle` feature lets the exact same pipeline code run under
+//! `shuttle`'s deterministic scheduler instead of real OS threads, so
+//! `pipeline`'s fan-out logic can be exhaustively tested without touching
+//! real threads or channels.
+
+#[cfg(not(feature = "shuttle"))]
+pub(crate) use std::{
+    sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender},
+    thread::{spawn, JoinHandle},
+};
+
+#[cfg(feature = "shuttle")]
+pub(crate) use shuttle::{
+    sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender},
+    thread::{spawn, JoinHandle},
+};
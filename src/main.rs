@@ -2,97 +2,434 @@
 use std::{
     eprintln,
     fs::File,
-    io::{Read, Seek, Write},
+    io::{ErrorKind, Read, Seek, Write},
+    net::TcpStream,
     path::PathBuf,
-    thread::{spawn, JoinHandle},
+    sync::Arc,
 };
 
 use argh::FromArgs;
-use bus::Bus;
+use flate2::{write::GzEncoder, Compression};
+use indicatif::{ProgressBar, ProgressStyle};
 use thiserror::Error;
 
+mod concurrency;
+mod network;
+mod pipeline;
+mod source;
+
+use concurrency::{spawn, sync_channel, JoinHandle};
+use source::{Generator, Source};
+
 #[derive(FromArgs, Debug)]
 /// Simple interface to write image to many files/devices at once, can also be used to backup to multiple locations
 struct Diode {
     #[argh(option, short = 'i')]
-    /// input file to read from
-    input: PathBuf,
+    /// input file to read from (not used with --listen)
+    input: Option<PathBuf>,
 
     #[argh(option, short = 'o')]
-    /// output file(s) to write to
-    output: Vec<PathBuf>,
+    /// output file(s) to write to; a `tcp://host:port` target streams to a remote `diode --listen`
+    output: Vec<OutputTarget>,
 
     #[argh(option, short = 'b', default = "64000")]
     /// set the block size to process data (default: 64000)
     block_size: usize,
 
-    #[argh(option, short = 'm', default = "20")]
-    /// set the amount of blocks to store in memory at a given time
-    block_buffer: usize,
+    #[argh(option, short = 'l', default = "20")]
+    /// max number of blocks an output's own queue may hold before it
+    /// starts applying backpressure (default: 20); see
+    /// `pipeline::dispatch_block` for the separate, much looser bound on
+    /// a stuck writer's backlog
+    max_lag: usize,
 
     #[argh(option, short = 'c')]
-    /// number of blocks to read, useful for generating random data
+    /// number of blocks to read, required for the `zero`/`random` sources
     block_count: Option<usize>,
+
+    #[argh(option, default = "Source::File")]
+    /// where to read blocks from: `file` (default), `zero`, or `random`
+    source: Source,
+
+    #[argh(option)]
+    /// seed the `random` source's RNG for a reproducible fill
+    seed: Option<u64>,
+
+    #[argh(option, short = 'z')]
+    /// compress each output as it is written, `gzip` or `zstd` (default: uncompressed); not supported alongside a tcp:// output
+    compress: Option<CompressionFormat>,
+
+    #[argh(option)]
+    /// compression level to use with --compress (defaults to the format's standard level)
+    compress_level: Option<i32>,
+
+    #[argh(switch, short = 'p')]
+    /// show a live progress bar/spinner on stderr while copying
+    progress: bool,
+
+    #[argh(option)]
+    /// run as a receiver: listen on `host:port` and write the incoming stream to the positional output file
+    listen: Option<String>,
+
+    #[argh(positional)]
+    /// output file to write to in --listen mode
+    listen_output: Option<PathBuf>,
+}
+
+/// Where a writer thread sends its bytes: a local file, or a remote
+/// `diode --listen` receiver over TCP.
+#[derive(Debug, Clone)]
+enum OutputTarget {
+    File(PathBuf),
+    Tcp(String),
+}
+
+impl std::fmt::Display for OutputTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Tcp(addr) => write!(f, "tcp://{addr}"),
+        }
+    }
+}
+
+impl std::str::FromStr for OutputTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("tcp://") {
+            Some(addr) if !addr.is_empty() => Ok(Self::Tcp(addr.to_string())),
+            Some(_) => Err("a tcp:// output requires a host:port".to_string()),
+            None => Ok(Self::File(PathBuf::from(s))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompressionFormat {
+    Gzip,
+    Zstd,
+}
+
+impl CompressionFormat {
+    /// The extension appended to an output path written in this format, so
+    /// backups land as `.gz`/`.zst` without a manual rename.
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Gzip => "gz",
+            Self::Zstd => "zst",
+        }
+    }
+}
+
+impl std::str::FromStr for CompressionFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gzip" | "gz" => Ok(Self::Gzip),
+            "zstd" | "zst" => Ok(Self::Zstd),
+            other => Err(format!("unknown compression format `{other}`, expected `gzip` or `zstd`")),
+        }
+    }
+}
+
+/// Appends the format's extension to `path`, unless it is already present.
+fn compressed_path(path: PathBuf, format: CompressionFormat) -> PathBuf {
+    let ext = format.extension();
+    if path.extension().and_then(|e| e.to_str()) == Some(ext) {
+        return path;
+    }
+
+    let mut name = path.into_os_string();
+    name.push(".");
+    name.push(ext);
+    PathBuf::from(name)
 }
 
+/// A writer's output stream: a local file (optionally wrapped in a
+/// streaming compressor) or a connected TCP socket to a remote
+/// `diode --listen` receiver, so callers can always just `Write` to it.
+///
+/// Compression currently only applies to file outputs; a TCP target is
+/// always sent raw, since the remote's `--listen` side doesn't negotiate a
+/// codec. `Diode::run` rejects `--compress` combined with a `tcp://` output
+/// up front, rather than silently compressing some outputs and not others.
+enum Sink {
+    Plain(File),
+    Gzip(GzEncoder<File>),
+    Zstd(zstd::Encoder<'static, File>),
+    Tcp(TcpStream),
+}
+
+/// What went wrong finalizing a [`Sink`], kept distinct from the write-time
+/// `Error` variants so the writer thread can report the right one.
+enum SinkFinishError {
+    Encoder,
+    Sync,
+}
+
+impl Sink {
+    fn new_file(
+        file: File,
+        compress: Option<CompressionFormat>,
+        level: Option<i32>,
+    ) -> std::io::Result<Self> {
+        Ok(match compress {
+            None => Self::Plain(file),
+            Some(CompressionFormat::Gzip) => {
+                let level = level.unwrap_or(Compression::default().level() as i32);
+                Self::Gzip(GzEncoder::new(file, Compression::new(level.clamp(0, 9) as u32)))
+            }
+            Some(CompressionFormat::Zstd) => {
+                Self::Zstd(zstd::Encoder::new(file, level.unwrap_or(0))?)
+            }
+        })
+    }
+
+    /// Flushes and finalizes the compressor (a no-op for uncompressed
+    /// output) and syncs the underlying file to disk, or half-closes the
+    /// write side of a TCP target so the remote sees a clean EOF.
+    fn finish(self) -> Result<(), SinkFinishError> {
+        let file = match self {
+            Self::Plain(file) => file,
+            Self::Gzip(encoder) => encoder.finish().map_err(|_| SinkFinishError::Encoder)?,
+            Self::Zstd(encoder) => encoder.finish().map_err(|_| SinkFinishError::Encoder)?,
+            Self::Tcp(stream) => {
+                return stream
+                    .shutdown(std::net::Shutdown::Write)
+                    .map_err(|_| SinkFinishError::Sync)
+            }
+        };
+
+        file.sync_all().map_err(|_| SinkFinishError::Sync)
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(file) => file.write(buf),
+            Self::Gzip(encoder) => encoder.write(buf),
+            Self::Zstd(encoder) => encoder.write(buf),
+            Self::Tcp(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Plain(file) => file.flush(),
+            Self::Gzip(encoder) => encoder.flush(),
+            Self::Zstd(encoder) => encoder.flush(),
+            Self::Tcp(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Fill `buf` to its full length from `source`, retrying on short reads and
+/// `Interrupted` errors (mirroring `std::io::copy`'s read loop), then
+/// truncate it to the number of bytes actually read. Without this, a
+/// `Read` impl that returns fewer bytes than requested (pipes, sockets,
+/// some block devices) would leave the tail of `buf` zero-filled and that
+/// padding would get broadcast to every writer. Generic over `Read` so the
+/// same loop backs both the local file reader and the `--listen` receiver.
+pub(crate) fn fill_block(source: &mut impl Read, buf: &mut Vec<u8>) -> std::io::Result<usize> {
+    let mut filled = 0;
+
+    while filled < buf.len() {
+        match source.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    buf.truncate(filled);
+    Ok(filled)
+}
+
+/// A block of data shared between the reader thread and every writer's
+/// queue without copying the underlying bytes.
+pub(crate) type Block = Arc<Vec<u8>>;
+
 impl Diode {
     fn run(self) -> Result<Status, Error> {
-        type ThreadResult = JoinHandle<Result<usize, Error>>;
+        type WriterResult = JoinHandle<Result<OutputStatus, Error>>;
+        type ReaderResult = JoinHandle<Result<usize, Error>>;
+
+        // Compression only applies to file outputs (see `Sink`'s doc
+        // comment); silently ignoring `--compress` for a `tcp://` output
+        // would leave the two outputs of a mixed run diverging with no
+        // indication why, so reject the combination up front instead.
+        if self.compress.is_some()
+            && self
+                .output
+                .iter()
+                .any(|output| matches!(output, OutputTarget::Tcp(_)))
+        {
+            return Err(Error::CompressionNotSupportedForTcpOutput);
+        }
 
-        let mut message_bus: Bus<Vec<u8>> = Bus::new(self.block_buffer);
-        let outputs = self.output.clone();
+        // Each output gets its own bounded queue instead of sharing a single
+        // lock-step broadcast bus, so one slow device no longer throttles
+        // every other writer down to its own pace; see `pipeline::dispatch_block`
+        // for how the reader-to-writer hand-off stays bounded without ever
+        // blocking on a writer that's merely slower than the reader.
+        let compress = self.compress;
+        let compress_level = self.compress_level;
+
+        let block_size = self.block_size;
+        let stream_name = match &self.input {
+            Some(path) => path.display().to_string(),
+            None => self.source.to_string(),
+        };
+        // An upper bound the receiver can show as "bytes expected": with
+        // `--block-count` it's `count * block_size` (exact for the zero/random
+        // sources, more than is sent if a file ends first); for a full-file
+        // copy it's the input's
+        // current size (the reader may still see fewer bytes if the file
+        // changes or shrinks underneath it).
+        let total_len = match self.block_count {
+            Some(count) => (count * self.block_size) as u64,
+            None => self
+                .input
+                .as_ref()
+                .and_then(|path| std::fs::metadata(path).ok())
+                .map(|meta| meta.len())
+                .unwrap_or(0),
+        };
+
+        let mut senders: Vec<pipeline::WriterQueue> = Vec::with_capacity(self.output.len());
+        let mut relay_threads: Vec<JoinHandle<()>> = Vec::with_capacity(self.output.len());
+        let writer_threads: Vec<WriterResult> = self
+            .output
+            .iter()
+            .cloned()
+            .map(|output_target| {
+                let (writer_tx, rx) = sync_channel::<Block>(self.max_lag);
+                let (queue, relay_thread) = pipeline::WriterQueue::spawn(writer_tx);
+                senders.push(queue);
+                relay_threads.push(relay_thread);
+
+                let stream_name = stream_name.clone();
 
-        let writer_threads: Vec<ThreadResult> = outputs
-            .into_iter()
-            .map(|output_path| {
-                let mut recv = message_bus.add_rx();
                 spawn(move || {
-                    let mut file = File::create(&output_path)
-                        .map_err(|_| Error::UnableToCreateFile(output_path))?;
+                    let label = output_target.to_string();
+
+                    let mut sink = match output_target {
+                        OutputTarget::File(path) => {
+                            let path = match compress {
+                                Some(format) => compressed_path(path, format),
+                                None => path,
+                            };
+
+                            let file = File::create(&path)
+                                .map_err(|_| Error::UnableToCreateFile(path.clone()))?;
+                            Sink::new_file(file, compress, compress_level)
+                                .map_err(|_| Error::UnableToInitEncoder(path.display().to_string()))?
+                        }
+                        OutputTarget::Tcp(addr) => Sink::Tcp(network::connect(
+                            &addr,
+                            block_size as u32,
+                            total_len,
+                            &stream_name,
+                        )?),
+                    };
+                    let mut bytes_written = 0;
 
                     loop {
-                        match recv.recv() {
+                        match rx.recv() {
                             Ok(bytes) => {
-                                file.write_all(&bytes)
+                                sink.write_all(&bytes)
                                     .map_err(|_| Error::UnableToWriteToBuffer)?;
+                                bytes_written += bytes.len();
                             }
                             Err(_) => {
-                                file.sync_all().map_err(|_| Error::UnableToSyncFiles)?;
+                                sink.finish().map_err(|err| match err {
+                                    SinkFinishError::Encoder => {
+                                        Error::UnableToFinalizeEncoder(label.clone())
+                                    }
+                                    SinkFinishError::Sync => Error::UnableToSyncFiles,
+                                })?;
                                 break;
                             }
                         }
                     }
 
-                    Ok(0)
+                    Ok(OutputStatus {
+                        label,
+                        bytes_written,
+                    })
                 })
             })
             .collect();
 
-        let reader_thread: ThreadResult = spawn(move || {
-            let mut file =
-                File::open(&self.input).map_err(|_| Error::UnableToOpenFile(self.input.clone()))?;
+        let reader_thread: ReaderResult = spawn(move || {
+            let mut generator = Generator::new(self.source, self.seed);
+
+            if generator.is_some() && self.block_count.is_none() {
+                return Err(Error::SourceRequiresBlockCount);
+            }
+
+            // Only the `file` source needs an actual input file opened; the
+            // synthetic sources generate their bytes on the fly below.
+            let mut file = match &generator {
+                Some(_) => None,
+                None => {
+                    let input = self.input.clone().ok_or(Error::MissingInput)?;
+                    Some(
+                        File::open(&input).map_err(|_| Error::UnableToOpenFile(input.clone()))?,
+                    )
+                }
+            };
+            let input = self.input.clone().unwrap_or_default();
+
+            // Dispatch a block to every writer's queue, dropping any writer
+            // whose queue is gone (its thread already hit a fatal error) so
+            // the reader keeps serving the survivors.
+            let mut dispatch = |block: Vec<u8>| pipeline::dispatch_block(&mut senders, block);
 
             let mut read = 0;
 
             match self.block_count {
                 Some(count) => {
+                    let bar = self.progress.then(progress_spinner);
+
                     for _ in 0..count {
                         let mut tmp_buf = vec![0; self.block_size];
-                        read += file
-                            .read(&mut tmp_buf)
-                            .map_err(|_| Error::UnableToReadBytesFrom(self.input.clone()))?;
-                        message_bus.broadcast(tmp_buf);
+                        read += match (&mut generator, &mut file) {
+                            (Some(generator), _) => {
+                                generator.fill(&mut tmp_buf);
+                                tmp_buf.len()
+                            }
+                            (None, Some(file)) => fill_block(file, &mut tmp_buf)
+                                .map_err(|_| Error::UnableToReadBytesFrom(input.clone()))?,
+                            (None, None) => unreachable!("file source always opens a file"),
+                        };
+                        dispatch(tmp_buf);
+
+                        if let Some(bar) = &bar {
+                            bar.set_message(format_bytes(read));
+                        }
+                    }
+
+                    if let Some(bar) = bar {
+                        bar.finish_and_clear();
                     }
                 }
                 None => {
+                    let file = file.as_mut().expect("file source always opens a file");
                     let full_len = file
                         .stream_len()
-                        .map_err(|_| Error::UnableToGetByteLen(self.input.clone()))?;
+                        .map_err(|_| Error::UnableToGetByteLen(input.clone()))?;
+
+                    let bar = self.progress.then(|| progress_bar(full_len));
 
                     loop {
                         let curr_pos = file
                             .stream_position()
-                            .map_err(|_| Error::UnableToGetCurrPos(self.input.clone()))?;
+                            .map_err(|_| Error::UnableToGetCurrPos(input.clone()))?;
 
                         if curr_pos < full_len {
                             let diff = (full_len - curr_pos) as usize;
@@ -103,17 +440,30 @@ impl Diode {
                                 vec![0; self.block_size]
                             };
 
-                            read += file
-                                .read(&mut tmp_buf)
-                                .map_err(|_| Error::UnableToReadBytesFrom(self.input.clone()))?;
-                            message_bus.broadcast(tmp_buf);
+                            read += fill_block(file, &mut tmp_buf)
+                                .map_err(|_| Error::UnableToReadBytesFrom(input.clone()))?;
+                            dispatch(tmp_buf);
+
+                            if let Some(bar) = &bar {
+                                bar.set_position(read as u64);
+                                bar.set_message(format_bytes(read));
+                            }
                         } else {
                             break;
                         }
                     }
+
+                    if let Some(bar) = bar {
+                        bar.finish_and_clear();
+                    }
                 }
             };
 
+            // Dropping `senders` (moved into this closure, and only borrowed
+            // by `dispatch`) at the end of this closure disconnects every
+            // relay's receiver, so each
+            // relay thread drains whatever it's still holding and then
+            // falls out of its own loop.
             Ok(read)
         });
 
@@ -122,27 +472,34 @@ impl Diode {
             .join()
             .map_err(|_| Error::FailedToJoinThreads)??;
 
-        let st = Status {
-            bytes_copied: bytes_read,
-            num_of_files: writer_threads.len(),
-        };
+        for handle in relay_threads {
+            handle.join().map_err(|_| Error::FailedToJoinThreads)?;
+        }
 
+        let mut outputs = Vec::with_capacity(writer_threads.len());
         for handle in writer_threads {
-            handle.join().map_err(|_| Error::FailedToJoinThreads)??;
+            outputs.push(handle.join().map_err(|_| Error::FailedToJoinThreads)??);
         }
 
-        Ok(st)
+        Ok(Status {
+            bytes_copied: bytes_read,
+            outputs,
+        })
     }
 }
 
 #[derive(Error, Debug)]
-enum Error {
+pub(crate) enum Error {
     #[error("Error ocuured while writing to buffer.")]
     UnableToWriteToBuffer,
     #[error("Error ocuured while syncing all files.")]
     UnableToSyncFiles,
     #[error("Error ocuured while creating file: {0}.")]
     UnableToCreateFile(PathBuf),
+    #[error("Error ocuured while initializing compressor for output: {0}.")]
+    UnableToInitEncoder(String),
+    #[error("Error ocuured while finalizing compressor for output: {0}.")]
+    UnableToFinalizeEncoder(String),
     #[error("Error ocuured while opening file: {0}.")]
     UnableToOpenFile(PathBuf),
     #[error("Error ocuured while reading bytes from file: {0}.")]
@@ -153,35 +510,206 @@ enum Error {
     UnableToGetByteLen(PathBuf),
     #[error("Error ocuured while waiting for threads.")]
     FailedToJoinThreads,
+    #[error("No --input given (and not running in --listen mode).")]
+    MissingInput,
+    #[error("--source zero/random has no natural end; pass --block-count to bound it.")]
+    SourceRequiresBlockCount,
+    #[error("--listen requires a positional output file to write the received stream to.")]
+    MissingListenOutput,
+    #[error("--compress is not supported for tcp:// outputs; drop --compress or the tcp:// output.")]
+    CompressionNotSupportedForTcpOutput,
+    #[error("Error ocuured while connecting to {0}.")]
+    UnableToConnectTo(String),
+    #[error("Error ocuured while sending the stream header to {0}.")]
+    UnableToSendHeader(String),
+    #[error("Error ocuured while binding listener on {0}.")]
+    UnableToBindListener(String),
+    #[error("Error ocuured while accepting a connection on {0}.")]
+    UnableToAcceptConnection(String),
+    #[error("Error ocuured while reading the stream header from {0}.")]
+    UnableToReadHeader(String),
+    #[error("Remote advertised a block size of {0} bytes, which is unreasonable (or zero); refusing to allocate for it.")]
+    RemoteBlockSizeTooLarge(u32),
+}
+
+/// Per-output progress, reported once a writer thread finishes; the live
+/// `--progress` display tracks only the reader's total, not these.
+pub(crate) struct OutputStatus {
+    pub(crate) label: String,
+    pub(crate) bytes_written: usize,
 }
 
-struct Status {
-    bytes_copied: usize,
-    num_of_files: usize,
+pub(crate) struct Status {
+    pub(crate) bytes_copied: usize,
+    pub(crate) outputs: Vec<OutputStatus>,
+}
+
+fn convert_bytes(bytes: usize) -> (usize, &'static str) {
+    if bytes < 1024 {
+        (bytes, "bytes")
+    } else if bytes < 1024 * 1024 {
+        (bytes / 1024, "KB")
+    } else if bytes < 1024 * 1024 * 1024 {
+        (bytes / (1024 * 1024), "MB")
+    } else {
+        (bytes / (1024 * 1024 * 1024), "GB")
+    }
+}
+
+/// Groups a non-negative integer's digits with thousands separators, e.g.
+/// `1234567` -> `"1,234,567"`.
+fn group_thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+
+    for (i, digit) in digits.chars().rev().enumerate() {
+        if i != 0 && i % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+
+    grouped.chars().rev().collect()
+}
+
+/// Human-readable byte count, e.g. `"292 KB"` or `"1,234 GB"`. Used for both
+/// the final `Status` summary and the live progress reporting.
+fn format_bytes(bytes: usize) -> String {
+    let (size, unit) = convert_bytes(bytes);
+    format!("{} {unit}", group_thousands(size))
 }
 
 impl std::fmt::Display for Status {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let convert_bytes = |bytes: usize| -> (usize, &str) {
-            if bytes < 1024 {
-                (bytes, "bytes")
-            } else if bytes < 1024 * 1024 {
-                (bytes / 1024, "KB")
-            } else if bytes < 1024 * 1024 * 1024 {
-                (bytes / (1024 * 1024), "MB")
-            } else {
-                (bytes / (1024 * 1024 * 1024), "GB")
-            }
-        };
+        write!(
+            f,
+            "{} copied to {} files.",
+            format_bytes(self.bytes_copied),
+            self.outputs.len()
+        )?;
 
-        let (size, unit) = convert_bytes(self.bytes_copied);
-        write!(f, "{size} {unit} copied to {} files.", self.num_of_files)
+        for output in &self.outputs {
+            write!(f, "\n  {}: {}", output.label, format_bytes(output.bytes_written))?;
+        }
+
+        Ok(())
     }
 }
 
+/// Builds a percentage bar with throughput and ETA, for use once the total
+/// number of bytes to copy is known up front.
+fn progress_bar(total_bytes: u64) -> ProgressBar {
+    let bar = ProgressBar::new(total_bytes);
+    bar.set_style(
+        ProgressStyle::with_template(
+            "[{elapsed_precise}] [{bar:40.cyan/blue}] {percent}% {msg} ({bytes_per_sec}, ETA {eta})",
+        )
+        .expect("static progress template is valid")
+        .progress_chars("=>-"),
+    );
+    bar
+}
+
+/// Builds a spinner with a running bytes-so-far count, used for
+/// `--block-count` runs. A full-file copy always uses [`progress_bar`]; an
+/// input whose length can't be read (an unseekable one) fails with
+/// `UnableToGetByteLen` and gets no spinner.
+fn progress_spinner() -> ProgressBar {
+    let bar = ProgressBar::new_spinner();
+    bar.set_style(
+        ProgressStyle::with_template("{spinner:.green} [{elapsed_precise}] {msg}")
+            .expect("static progress template is valid"),
+    );
+    bar.enable_steady_tick(std::time::Duration::from_millis(100));
+    bar
+}
+
 fn main() {
-    match argh::from_env::<Diode>().run() {
+    let diode = argh::from_env::<Diode>();
+
+    let result = match &diode.listen {
+        Some(addr) => diode
+            .listen_output
+            .clone()
+            .ok_or(Error::MissingListenOutput)
+            .and_then(|output| network::serve(addr, output)),
+        None => diode.run(),
+    };
+
+    match result {
         Ok(st) => println!("{st}"),
         Err(err) => eprintln!("{err}"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, ErrorKind, Read};
+
+    use super::fill_block;
+
+    /// A `Read` that hands back `chunks` one at a time, one `read` call
+    /// per chunk, with a single `Interrupted` error injected right before
+    /// `interrupt_before` — modelling a pipe or socket that both splits a
+    /// single block across several short reads and gets interrupted along
+    /// the way, the exact case `fill_block` exists to survive.
+    struct ScriptedReader {
+        chunks: Vec<&'static [u8]>,
+        interrupt_before: usize,
+    }
+
+    impl Read for ScriptedReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.interrupt_before == 0 {
+                self.interrupt_before = usize::MAX;
+                return Err(io::Error::from(ErrorKind::Interrupted));
+            }
+            self.interrupt_before = self.interrupt_before.saturating_sub(1);
+
+            match self.chunks.first() {
+                Some(chunk) => {
+                    let n = chunk.len().min(buf.len());
+                    buf[..n].copy_from_slice(&chunk[..n]);
+                    self.chunks[0] = &chunk[n..];
+                    if self.chunks[0].is_empty() {
+                        self.chunks.remove(0);
+                    }
+                    Ok(n)
+                }
+                None => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn fill_block_assembles_short_reads_and_retries_interrupted() {
+        let mut source = ScriptedReader {
+            chunks: vec![b"hel", b"lo, ", b"world"],
+            interrupt_before: 1,
+        };
+        let mut buf = vec![0; 12];
+
+        let filled = fill_block(&mut source, &mut buf).expect("reads should succeed");
+
+        assert_eq!(filled, 12);
+        assert_eq!(&buf, b"hello, world");
+    }
+
+    #[test]
+    fn fill_block_truncates_to_what_eof_actually_delivered() {
+        let mut source = ScriptedReader {
+            chunks: vec![b"short"],
+            interrupt_before: usize::MAX,
+        };
+        let mut buf = vec![0xFF; 12];
+
+        let filled = fill_block(&mut source, &mut buf).expect("reads should succeed");
+
+        assert_eq!(filled, 5);
+        assert_eq!(
+            buf.as_slice(),
+            b"short",
+            "buf must be truncated, not left zero/0xFF-padded"
+        );
+    }
+}
@@ -0,0 +1,68 @@
+//! Where a block's bytes come from, kept separate from the reader loop in
+//! [`crate::Diode::run`] so it doesn't need to know whether it's copying a
+//! real file or generating synthetic data.
+
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum Source {
+    #[default]
+    File,
+    Zero,
+    Random,
+}
+
+impl std::str::FromStr for Source {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "file" => Ok(Self::File),
+            "zero" => Ok(Self::Zero),
+            "random" => Ok(Self::Random),
+            other => Err(format!(
+                "unknown source `{other}`, expected `file`, `zero`, or `random`"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::File => write!(f, "file"),
+            Self::Zero => write!(f, "zero"),
+            Self::Random => write!(f, "random"),
+        }
+    }
+}
+
+/// A synthetic block generator backing the `zero`/`random` sources. `None`
+/// (via [`Generator::new`]) means the reader should fall back to reading
+/// an actual input file instead.
+pub(crate) enum Generator {
+    Zero,
+    Random(Box<StdRng>),
+}
+
+impl Generator {
+    pub(crate) fn new(source: Source, seed: Option<u64>) -> Option<Self> {
+        match source {
+            Source::File => None,
+            Source::Zero => Some(Self::Zero),
+            Source::Random => Some(Self::Random(Box::new(match seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            }))),
+        }
+    }
+
+    /// Fills `buf` with this generator's bytes, the synthetic equivalent of
+    /// [`crate::fill_block`] for a real file.
+    pub(crate) fn fill(&mut self, buf: &mut [u8]) {
+        match self {
+            Self::Zero => buf.fill(0),
+            Self::Random(rng) => rng.fill_bytes(buf),
+        }
+    }
+}